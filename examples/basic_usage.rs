@@ -1,14 +1,16 @@
 use anyhow::Result;
-use clipper::ClipEmbedder;
+use clipper::{ClipEmbedder, ModelKind, Precision, cosine_similarity};
 
 fn main() -> Result<()> {
     // Initialize the CLIP embedder
     // This handles all the model downloading and setup
     println!("Initializing CLIP embedder...");
     let embedder = ClipEmbedder::new(
-        None,    // model_path: Use default (downloads from HuggingFace)
-        None,    // tokenizer_path: Use default (downloads from HuggingFace)  
-        false,   // use_cpu: Use GPU if available, otherwise CPU
+        None,                         // model_path: Use default (downloads from HuggingFace)
+        None,                         // tokenizer_path: Use default (downloads from HuggingFace)
+        false,                        // use_cpu: Use GPU if available, otherwise CPU
+        ModelKind::ClipVitBasePatch32, // model_kind: Use the default CLIP backbone
+        Precision::F32,               // precision: Full precision weights and inputs
     )?;
     
     println!("CLIP embedder initialized successfully!\n");
@@ -56,16 +58,3 @@ fn main() -> Result<()> {
 
     Ok(())
 }
-
-// Helper function to compute cosine similarity between two embeddings
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-    
-    if norm_a == 0.0 || norm_b == 0.0 {
-        0.0
-    } else {
-        dot_product / (norm_a * norm_b)
-    }
-}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use clipper::ClipEmbedder;
+use clipper::{ClipEmbedder, ModelKind, Precision, cosine_similarity};
 use std::fs;
 
 fn main() -> Result<()> {
@@ -8,7 +8,7 @@ fn main() -> Result<()> {
 
     // Initialize embedder
     println!("Initializing CLIP embedder...");
-    let embedder = ClipEmbedder::new(None, None, false)?;
+    let embedder = ClipEmbedder::new(None, None, false, ModelKind::ClipVitBasePatch32, Precision::F32)?;
     println!("✅ Embedder initialized successfully!\n");
 
     let image_path = "assets/stable-diffusion-xl.jpg";
@@ -69,15 +69,3 @@ fn main() -> Result<()> {
 
     Ok(())
 }
-
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-    
-    if norm_a == 0.0 || norm_b == 0.0 {
-        0.0
-    } else {
-        dot_product / (norm_a * norm_b)
-    }
-}
@@ -0,0 +1,550 @@
+//! A persistent index of `(id, embedding)` pairs with nearest-neighbor search, so embeddings
+//! produced by a [`ClipEmbedder`] can back image/text retrieval instead of just being raw
+//! vectors the caller has to store and compare themselves.
+
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::ClipEmbedder;
+
+/// Number of centroids trained per product-quantization subspace
+const PQ_CENTROIDS: usize = 256;
+/// K-means iterations used to train each subspace's codebook
+const PQ_KMEANS_ITERATIONS: usize = 10;
+
+/// An in-memory index of embeddings, persistable to disk, supporting cosine-similarity search
+pub struct EmbeddingIndex {
+    dim: usize,
+    ids: Vec<String>,
+    /// Flattened `(ids.len(), dim)` row-major matrix, kept even after `quantize` so exact
+    /// (unquantized) search and re-quantization at a different `m` both stay possible
+    vectors: Vec<f32>,
+    /// L2 norm of each row in `vectors`, precomputed so `search` avoids recomputing it per query
+    norms: Vec<f32>,
+    /// Product-quantization codebooks + codes, if `quantize` has been called
+    pq: Option<ProductQuantizer>,
+}
+
+/// Trained product-quantization codebooks and the per-vector codes they encode
+struct ProductQuantizer {
+    /// Number of subspaces the embedding is split into
+    m: usize,
+    /// Centroids per subspace (`PQ_CENTROIDS`, or the vector count if smaller; fits in a `u8` code)
+    k: usize,
+    /// Dimensionality of each subspace (`dim / m`)
+    sub_dim: usize,
+    /// Flattened `(m, k, sub_dim)` codebooks
+    codebooks: Vec<f32>,
+    /// Flattened `(ids.len(), m)` centroid ids, one byte per subspace per vector
+    codes: Vec<u8>,
+}
+
+impl EmbeddingIndex {
+    /// Create an empty index for embeddings of the given dimensionality
+    pub fn new(dim: usize) -> Self {
+        EmbeddingIndex {
+            dim,
+            ids: Vec::new(),
+            vectors: Vec::new(),
+            norms: Vec::new(),
+            pq: None,
+        }
+    }
+
+    /// Number of embeddings currently in the index
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Whether the index has no embeddings
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Add an `(id, embedding)` pair to the index, invalidating any existing product quantization
+    /// (call [`EmbeddingIndex::quantize`] again to requantize; `search` falls back to exact
+    /// scoring until then)
+    pub fn add(&mut self, id: impl Into<String>, embedding: &[f32]) -> Result<()> {
+        if embedding.len() != self.dim {
+            bail!(
+                "embedding has {} dimensions, expected {}",
+                embedding.len(),
+                self.dim
+            );
+        }
+        self.ids.push(id.into());
+        self.vectors.extend_from_slice(embedding);
+        self.norms.push(l2_norm(embedding));
+        self.pq = None;
+        Ok(())
+    }
+
+    /// Return the top-`k` ids by cosine similarity to `query`
+    ///
+    /// Uses asymmetric distance computation against the product-quantization codes if
+    /// [`EmbeddingIndex::quantize`] has been called, otherwise scores the exact vectors.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(String, f32)> {
+        match &self.pq {
+            Some(pq) => self.search_quantized(pq, query, k),
+            None => self.search_exact(query, k),
+        }
+    }
+
+    fn search_exact(&self, query: &[f32], k: usize) -> Vec<(String, f32)> {
+        let query_norm = l2_norm(query);
+        let scored = (0..self.ids.len()).map(|i| {
+            let row = self.row(i);
+            let dot: f32 = row.iter().zip(query).map(|(a, b)| a * b).sum();
+            (i, dot, query_norm * self.norms[i])
+        });
+        self.rank(scored, k)
+    }
+
+    /// Score every vector as `m` codebook lookups summed, rather than reconstructing it: for
+    /// each subspace, precompute the dot product of the query subvector against every centroid
+    /// in that subspace once, then a stored vector's score is just a sum of `m` table lookups.
+    fn search_quantized(&self, pq: &ProductQuantizer, query: &[f32], k: usize) -> Vec<(String, f32)> {
+        let query_norm = l2_norm(query);
+        let mut table = vec![0f32; pq.m * pq.k];
+        for sub in 0..pq.m {
+            let query_sub = &query[sub * pq.sub_dim..(sub + 1) * pq.sub_dim];
+            for c in 0..pq.k {
+                table[sub * pq.k + c] = dot(query_sub, pq.centroid(sub, c));
+            }
+        }
+
+        let scored = (0..self.ids.len()).map(|i| {
+            let dot: f32 = (0..pq.m)
+                .map(|sub| table[sub * pq.k + pq.codes[i * pq.m + sub] as usize])
+                .sum();
+            (i, dot, query_norm * self.norms[i])
+        });
+        self.rank(scored, k)
+    }
+
+    fn rank(&self, scored: impl Iterator<Item = (usize, f32, f32)>, k: usize) -> Vec<(String, f32)> {
+        let mut scored: Vec<(String, f32)> = scored
+            .map(|(i, dot, denom)| {
+                let score = if denom == 0.0 { 0.0 } else { dot / denom };
+                (self.ids[i].clone(), score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(k);
+        scored
+    }
+
+    /// Compress the index with product quantization: each embedding is split into `m` contiguous
+    /// subvectors, a codebook of `PQ_CENTROIDS` centroids (or one per vector, if the index holds
+    /// fewer) is trained per subspace with k-means, and each vector is then stored as `m` centroid ids (one byte each) instead of `dim` floats.
+    /// The exact vectors are kept, so re-quantizing at a different `m` or falling back to exact
+    /// search both remain possible.
+    pub fn quantize(&mut self, m: usize) -> Result<()> {
+        if m == 0 || self.dim % m != 0 {
+            bail!("m={} must evenly divide the embedding dimension {}", m, self.dim);
+        }
+        if self.is_empty() {
+            bail!("cannot train product quantization codebooks on an empty index");
+        }
+
+        let sub_dim = self.dim / m;
+        let k = PQ_CENTROIDS.min(self.len());
+        let mut codebooks = vec![0f32; m * k * sub_dim];
+        let mut codes = vec![0u8; self.len() * m];
+
+        for sub in 0..m {
+            let subvectors: Vec<&[f32]> = (0..self.len())
+                .map(|i| &self.row(i)[sub * sub_dim..(sub + 1) * sub_dim])
+                .collect();
+            let centroids = kmeans(&subvectors, k, PQ_KMEANS_ITERATIONS);
+            for (c, centroid) in centroids.iter().enumerate() {
+                codebooks[(sub * k + c) * sub_dim..(sub * k + c + 1) * sub_dim]
+                    .copy_from_slice(centroid);
+            }
+            for (i, subvector) in subvectors.iter().enumerate() {
+                codes[i * m + sub] = nearest_centroid(subvector, &centroids) as u8;
+            }
+        }
+
+        self.pq = Some(ProductQuantizer {
+            m,
+            k,
+            sub_dim,
+            codebooks,
+            codes,
+        });
+        Ok(())
+    }
+
+    /// Persist the index as a small binary format: `dim: u32`, `count: u32`, each row's
+    /// precomputed L2 norm (`count` x f32), a `has_pq: u8` flag, then either the full row-major
+    /// f32 matrix (if not quantized) or `m: u32`, `k: u32`, the `(m, k, sub_dim)` codebooks, and
+    /// the `(count, m)` codes (if quantized), and finally a length-prefixed id table (`len: u32`
+    /// + utf8 bytes per id).
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&(self.dim as u32).to_le_bytes())?;
+        writer.write_all(&(self.ids.len() as u32).to_le_bytes())?;
+        for value in &self.norms {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        match &self.pq {
+            None => {
+                writer.write_all(&[0u8])?;
+                for value in &self.vectors {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+            Some(pq) => {
+                writer.write_all(&[1u8])?;
+                writer.write_all(&(pq.m as u32).to_le_bytes())?;
+                writer.write_all(&(pq.k as u32).to_le_bytes())?;
+                for value in &pq.codebooks {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+                writer.write_all(&pq.codes)?;
+            }
+        }
+
+        for id in &self.ids {
+            let bytes = id.as_bytes();
+            writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            writer.write_all(bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Load an index previously written by [`EmbeddingIndex::save`]
+    ///
+    /// A quantized index doesn't persist the exact vectors (see `save`); they're reconstructed
+    /// here from the codebooks instead, so exact search and `add` still work, just against the
+    /// quantized approximation of each row rather than its original value.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let dim = read_u32(&mut reader)? as usize;
+        let count = read_u32(&mut reader)? as usize;
+
+        let mut buf = [0u8; 4];
+        let mut norms = vec![0f32; count];
+        for value in norms.iter_mut() {
+            reader.read_exact(&mut buf)?;
+            *value = f32::from_le_bytes(buf);
+        }
+
+        let mut has_pq = [0u8; 1];
+        reader.read_exact(&mut has_pq)?;
+        let (vectors, pq) = if has_pq[0] == 0 {
+            let mut vectors = vec![0f32; dim * count];
+            for value in vectors.iter_mut() {
+                reader.read_exact(&mut buf)?;
+                *value = f32::from_le_bytes(buf);
+            }
+            (vectors, None)
+        } else {
+            let m = read_u32(&mut reader)? as usize;
+            let k = read_u32(&mut reader)? as usize;
+            let sub_dim = dim / m;
+
+            let mut codebooks = vec![0f32; m * k * sub_dim];
+            for value in codebooks.iter_mut() {
+                reader.read_exact(&mut buf)?;
+                *value = f32::from_le_bytes(buf);
+            }
+
+            let mut codes = vec![0u8; count * m];
+            reader.read_exact(&mut codes)?;
+
+            let pq = ProductQuantizer {
+                m,
+                k,
+                sub_dim,
+                codebooks,
+                codes,
+            };
+            let mut vectors = vec![0f32; dim * count];
+            for i in 0..count {
+                for sub in 0..m {
+                    let code = pq.codes[i * m + sub] as usize;
+                    vectors[i * dim + sub * sub_dim..i * dim + (sub + 1) * sub_dim]
+                        .copy_from_slice(pq.centroid(sub, code));
+                }
+            }
+            (vectors, Some(pq))
+        };
+
+        let mut ids = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = read_u32(&mut reader)? as usize;
+            let mut id_bytes = vec![0u8; len];
+            reader.read_exact(&mut id_bytes)?;
+            ids.push(String::from_utf8(id_bytes).context("embedding index id is not valid utf8")?);
+        }
+
+        Ok(EmbeddingIndex {
+            dim,
+            ids,
+            vectors,
+            norms,
+            pq,
+        })
+    }
+
+    fn row(&self, i: usize) -> &[f32] {
+        &self.vectors[i * self.dim..(i + 1) * self.dim]
+    }
+}
+
+impl ProductQuantizer {
+    fn centroid(&self, sub: usize, c: usize) -> &[f32] {
+        let start = (sub * self.k + c) * self.sub_dim;
+        &self.codebooks[start..start + self.sub_dim]
+    }
+}
+
+fn l2_norm(embedding: &[f32]) -> f32 {
+    embedding.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+fn nearest_centroid(point: &[f32], centroids: &[Vec<f32>]) -> usize {
+    centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, squared_distance(point, c)))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Train `k` centroids over `points` with a fixed number of Lloyd's-algorithm iterations
+///
+/// Centroids are seeded deterministically (evenly spaced samples from `points`) rather than
+/// randomly, so repeated `quantize` calls over the same data reproduce the same codebook.
+fn kmeans(points: &[&[f32]], k: usize, iterations: usize) -> Vec<Vec<f32>> {
+    let k = k.min(points.len()).max(1);
+    let dim = points[0].len();
+    let mut centroids: Vec<Vec<f32>> = (0..k)
+        .map(|i| points[i * points.len() / k].to_vec())
+        .collect();
+
+    for _ in 0..iterations {
+        let mut sums = vec![vec![0f32; dim]; k];
+        let mut counts = vec![0usize; k];
+
+        for point in points {
+            let nearest = nearest_centroid(point, &centroids);
+            counts[nearest] += 1;
+            for (sum, value) in sums[nearest].iter_mut().zip(*point) {
+                *sum += value;
+            }
+        }
+
+        for i in 0..k {
+            if counts[i] > 0 {
+                for value in sums[i].iter_mut() {
+                    *value /= counts[i] as f32;
+                }
+                centroids[i] = std::mem::take(&mut sums[i]);
+            }
+        }
+    }
+
+    centroids
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Embed `query` with `embedder` and return the top-`k` nearest neighbors in `index`
+pub fn search_by_text(
+    embedder: &ClipEmbedder,
+    index: &EmbeddingIndex,
+    query: &str,
+    k: usize,
+) -> Result<Vec<(String, f32)>> {
+    let embedding = embedder.get_text_embedding(query)?;
+    Ok(index.search(&embedding, k))
+}
+
+/// Embed the image at `image_path` with `embedder` and return the top-`k` nearest neighbors in
+/// `index`
+pub fn search_by_image(
+    embedder: &ClipEmbedder,
+    index: &EmbeddingIndex,
+    image_path: &str,
+    k: usize,
+) -> Result<Vec<(String, f32)>> {
+    let embedding = embedder.get_image_embedding(image_path)?;
+    Ok(index.search(&embedding, k))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_with(vectors: &[(&str, [f32; 4])]) -> EmbeddingIndex {
+        let mut index = EmbeddingIndex::new(4);
+        for (id, embedding) in vectors {
+            index.add(*id, embedding).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn add_then_search_ranks_by_cosine_similarity() {
+        let index = index_with(&[
+            ("a", [1.0, 0.0, 0.0, 0.0]),
+            ("b", [0.0, 1.0, 0.0, 0.0]),
+            ("c", [0.9, 0.1, 0.0, 0.0]),
+        ]);
+
+        let results = index.search(&[1.0, 0.0, 0.0, 0.0], 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "a");
+        assert_eq!(results[1].0, "c");
+    }
+
+    #[test]
+    fn add_rejects_wrong_dimension() {
+        let mut index = EmbeddingIndex::new(4);
+        assert!(index.add("a", &[1.0, 2.0, 3.0]).is_err());
+    }
+
+    #[test]
+    fn save_then_load_roundtrips_exact_search() {
+        let index = index_with(&[
+            ("a", [1.0, 0.0, 0.0, 0.0]),
+            ("b", [0.0, 1.0, 0.0, 0.0]),
+        ]);
+
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("clipper-index-test-{}.bin", std::process::id()));
+        index.save(&path).unwrap();
+        let loaded = EmbeddingIndex::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        let results = loaded.search(&[1.0, 0.0, 0.0, 0.0], 1);
+        assert_eq!(results[0].0, "a");
+    }
+
+    #[test]
+    fn quantize_then_search_quantized_finds_nearest() {
+        let mut index = index_with(&[
+            ("a", [1.0, 0.0, 0.0, 0.0]),
+            ("b", [0.0, 1.0, 0.0, 0.0]),
+            ("c", [0.0, 0.0, 1.0, 0.0]),
+            ("d", [0.0, 0.0, 0.0, 1.0]),
+        ]);
+
+        index.quantize(2).unwrap();
+        let results = index.search(&[1.0, 0.0, 0.0, 0.0], 1);
+        assert_eq!(results[0].0, "a");
+    }
+
+    #[test]
+    fn add_after_quantize_invalidates_pq_and_falls_back_to_exact() {
+        let mut index = index_with(&[
+            ("a", [1.0, 0.0, 0.0, 0.0]),
+            ("b", [0.0, 1.0, 0.0, 0.0]),
+            ("c", [0.0, 0.0, 1.0, 0.0]),
+            ("d", [0.0, 0.0, 0.0, 1.0]),
+        ]);
+        index.quantize(2).unwrap();
+
+        index.add("e", &[0.0, 0.0, 0.0, 0.9]).unwrap();
+
+        // Must not panic indexing stale PQ codes against the new, longer id list.
+        let results = index.search(&[0.0, 0.0, 0.0, 1.0], 2);
+        assert_eq!(results[0].0, "d");
+        assert_eq!(results[1].0, "e");
+    }
+
+    #[test]
+    fn save_then_load_quantized_index_shrinks_on_disk() {
+        let mut index = index_with(&[
+            ("a", [1.0, 0.0, 0.0, 0.0]),
+            ("b", [0.0, 1.0, 0.0, 0.0]),
+            ("c", [0.0, 0.0, 1.0, 0.0]),
+            ("d", [0.0, 0.0, 0.0, 1.0]),
+        ]);
+
+        let dir = std::env::temp_dir();
+        let unquantized_path = dir.join(format!("clipper-index-test-uq-{}.bin", std::process::id()));
+        index.save(&unquantized_path).unwrap();
+        let unquantized_size = std::fs::metadata(&unquantized_path).unwrap().len();
+        std::fs::remove_file(&unquantized_path).unwrap();
+
+        index.quantize(2).unwrap();
+        let quantized_path = dir.join(format!("clipper-index-test-q-{}.bin", std::process::id()));
+        index.save(&quantized_path).unwrap();
+        let quantized_size = std::fs::metadata(&quantized_path).unwrap().len();
+
+        let loaded = EmbeddingIndex::load(&quantized_path).unwrap();
+        std::fs::remove_file(&quantized_path).unwrap();
+
+        assert!(quantized_size < unquantized_size);
+        assert_eq!(loaded.len(), 4);
+        // Quantized search still works after a save/load roundtrip.
+        let results = loaded.search(&[1.0, 0.0, 0.0, 0.0], 1);
+        assert_eq!(results[0].0, "a");
+    }
+
+    #[test]
+    fn load_quantized_then_add_then_search_keeps_preexisting_rows_findable() {
+        let mut index = index_with(&[
+            ("a", [1.0, 0.0, 0.0, 0.0]),
+            ("b", [0.0, 1.0, 0.0, 0.0]),
+            ("c", [0.0, 0.0, 1.0, 0.0]),
+            ("d", [0.0, 0.0, 0.0, 1.0]),
+        ]);
+        index.quantize(2).unwrap();
+
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("clipper-index-test-load-add-{}.bin", std::process::id()));
+        index.save(&path).unwrap();
+        let mut loaded = EmbeddingIndex::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        loaded.add("e", &[0.9, 0.1, 0.0, 0.0]).unwrap();
+
+        // "a" must still score near 1.0, not the 0.0 a zero-reconstructed vector would give.
+        let results = loaded.search(&[1.0, 0.0, 0.0, 0.0], 1);
+        assert_eq!(results[0].0, "a");
+        assert!(results[0].1 > 0.9);
+    }
+
+    #[test]
+    fn load_quantized_then_requantize_does_not_bake_in_zero_vectors() {
+        let mut index = index_with(&[
+            ("a", [1.0, 0.0, 0.0, 0.0]),
+            ("b", [0.0, 1.0, 0.0, 0.0]),
+            ("c", [0.0, 0.0, 1.0, 0.0]),
+            ("d", [0.0, 0.0, 0.0, 1.0]),
+        ]);
+        index.quantize(2).unwrap();
+
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("clipper-index-test-load-req-{}.bin", std::process::id()));
+        index.save(&path).unwrap();
+        let mut loaded = EmbeddingIndex::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        loaded.quantize(2).unwrap();
+        let results = loaded.search(&[1.0, 0.0, 0.0, 0.0], 1);
+        assert_eq!(results[0].0, "a");
+    }
+}
@@ -2,89 +2,416 @@ use anyhow::Result;
 use candle_core::{DType, Device, Tensor};
 use candle_core::utils::{cuda_is_available, metal_is_available};
 use candle_nn::VarBuilder;
-use candle_transformers::models::clip;
+use candle_transformers::models::{clip, siglip};
 use tokenizers::Tokenizer;
 
-/// A CLIP model wrapper that provides easy access to image and text embeddings
+pub mod index;
+
+/// Which backbone architecture a `ClipEmbedder` loads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelKind {
+    /// The original OpenAI CLIP ViT-B/32 checkpoint
+    ClipVitBasePatch32,
+    /// A SigLIP (sigmoid-loss CLIP) checkpoint
+    SigLip(SigLipVariant),
+}
+
+impl Default for ModelKind {
+    fn default() -> Self {
+        ModelKind::ClipVitBasePatch32
+    }
+}
+
+/// SigLIP checkpoints differ in input resolution; pick the one matching the weights in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigLipVariant {
+    Base224,
+    Base384,
+}
+
+impl SigLipVariant {
+    fn hf_repo(&self) -> &'static str {
+        match self {
+            SigLipVariant::Base224 => "google/siglip-base-patch16-224",
+            SigLipVariant::Base384 => "google/siglip-base-patch16-384",
+        }
+    }
+
+    fn config(&self) -> siglip::Config {
+        match self {
+            SigLipVariant::Base224 => siglip::Config::base_patch16_224(),
+            SigLipVariant::Base384 => siglip::Config::base_patch16_384(),
+        }
+    }
+}
+
+/// Numeric precision used for model weights and inputs; [`ClipEmbedder::new`] falls back to
+/// [`Precision::F32`] on CPU regardless of the requested precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    F16,
+    Bf16,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision::F32
+    }
+}
+
+impl Precision {
+    fn into_dtype(self) -> DType {
+        match self {
+            Precision::F32 => DType::F32,
+            Precision::F16 => DType::F16,
+            Precision::Bf16 => DType::BF16,
+        }
+    }
+}
+
+/// The loaded backbone: either CLIP's softmax contrastive model or SigLIP's sigmoid one
+enum Backbone {
+    Clip {
+        model: clip::ClipModel,
+        config: clip::ClipConfig,
+    },
+    SigLip {
+        model: siglip::Model,
+        config: siglip::Config,
+    },
+}
+
+impl Backbone {
+    fn image_size(&self) -> usize {
+        match self {
+            Backbone::Clip { config, .. } => config.image_size,
+            Backbone::SigLip { config, .. } => config.vision_config.image_size,
+        }
+    }
+
+    /// The resize/normalization settings this backbone was trained with
+    fn preprocessing(&self) -> Preprocessing {
+        match self {
+            Backbone::Clip { .. } => Preprocessing::clip(self.image_size()),
+            Backbone::SigLip { .. } => Preprocessing::siglip(self.image_size()),
+        }
+    }
+}
+
+/// Resize/crop strategy and per-channel normalization applied before the vision encoder
+#[derive(Debug, Clone, Copy)]
+struct Preprocessing {
+    image_size: usize,
+    mean: [f32; 3],
+    std: [f32; 3],
+    center_crop: bool,
+}
+
+impl Preprocessing {
+    /// OpenAI CLIP's preprocessing: resize shorter side + center crop, CLIP's per-channel mean/std
+    fn clip(image_size: usize) -> Self {
+        Preprocessing {
+            image_size,
+            mean: [0.48145466, 0.4578275, 0.40821073],
+            std: [0.26862954, 0.26130258, 0.27577711],
+            center_crop: true,
+        }
+    }
+
+    /// SigLIP's preprocessing: a direct resize to a square with no crop, flat `[-1, 1]` normalization
+    fn siglip(image_size: usize) -> Self {
+        Preprocessing {
+            image_size,
+            mean: [0.5, 0.5, 0.5],
+            std: [0.5, 0.5, 0.5],
+            center_crop: false,
+        }
+    }
+}
+
+/// A CLIP/SigLIP model wrapper that provides easy access to image and text embeddings
 pub struct ClipEmbedder {
-    model: clip::ClipModel,
+    backbone: Backbone,
     tokenizer: Tokenizer,
-    config: clip::ClipConfig,
     device: Device,
+    dtype: DType,
 }
 
 impl ClipEmbedder {
     /// Create a new ClipEmbedder instance
-    /// 
+    ///
     /// # Arguments
     /// * `model_path` - Optional path to the model file. If None, downloads from HuggingFace
     /// * `tokenizer_path` - Optional path to the tokenizer file. If None, downloads from HuggingFace
     /// * `use_cpu` - Whether to force CPU usage instead of GPU
-    pub fn new(model_path: Option<String>, tokenizer_path: Option<String>, use_cpu: bool) -> Result<Self> {
+    /// * `model_kind` - Which backbone to load (CLIP or a SigLIP variant)
+    /// * `precision` - Numeric precision for weights and inputs (forced to F32 on CPU)
+    pub fn new(
+        model_path: Option<String>,
+        tokenizer_path: Option<String>,
+        use_cpu: bool,
+        model_kind: ModelKind,
+        precision: Precision,
+    ) -> Result<Self> {
         let device = get_device(use_cpu)?;
-        
-        let model_file = match model_path {
-            None => {
-                let api = hf_hub::api::sync::Api::new()?;
-                let api = api.repo(hf_hub::Repo::with_revision(
-                    "openai/clip-vit-base-patch32".to_string(),
-                    hf_hub::RepoType::Model,
-                    "refs/pr/15".to_string(),
-                ));
-                api.get("model.safetensors")?
-            }
-            Some(model) => model.into(),
-        };
-        
-        let tokenizer = get_tokenizer(tokenizer_path)?;
-        let config = clip::ClipConfig::vit_base_patch32();
-        
-        let vb = unsafe { 
-            VarBuilder::from_mmaped_safetensors(&[model_file], DType::F32, &device)? 
+        let dtype = match device {
+            Device::Cpu => DType::F32,
+            _ => precision.into_dtype(),
         };
-        let model = clip::ClipModel::new(vb, &config)?;
-        
-        Ok(ClipEmbedder {
-            model,
-            tokenizer,
-            config,
-            device,
-        })
-    }
-    
+
+        match model_kind {
+            ModelKind::ClipVitBasePatch32 => {
+                let repo_id = "openai/clip-vit-base-patch32";
+                let revision = "refs/pr/15";
+                let model_file = match model_path {
+                    None => {
+                        let api = hf_hub::api::sync::Api::new()?;
+                        let api = api.repo(hf_hub::Repo::with_revision(
+                            repo_id.to_string(),
+                            hf_hub::RepoType::Model,
+                            revision.to_string(),
+                        ));
+                        api.get("model.safetensors")?
+                    }
+                    Some(model) => model.into(),
+                };
+
+                let tokenizer = get_tokenizer(tokenizer_path, repo_id, revision)?;
+                let config = clip::ClipConfig::vit_base_patch32();
+
+                let vb = unsafe {
+                    VarBuilder::from_mmaped_safetensors(&[model_file], dtype, &device)?
+                };
+                let model = clip::ClipModel::new(vb, &config)?;
+
+                Ok(ClipEmbedder {
+                    backbone: Backbone::Clip { model, config },
+                    tokenizer,
+                    device,
+                    dtype,
+                })
+            }
+            ModelKind::SigLip(variant) => {
+                let repo_id = variant.hf_repo();
+                let model_file = match model_path {
+                    None => {
+                        let api = hf_hub::api::sync::Api::new()?;
+                        let api = api.repo(hf_hub::Repo::new(repo_id.to_string(), hf_hub::RepoType::Model));
+                        api.get("model.safetensors")?
+                    }
+                    Some(model) => model.into(),
+                };
+
+                let tokenizer = get_tokenizer(tokenizer_path, repo_id, "main")?;
+                let config = variant.config();
+
+                let vb = unsafe {
+                    VarBuilder::from_mmaped_safetensors(&[model_file], dtype, &device)?
+                };
+                let model = siglip::Model::new(&config, vb)?;
+
+                Ok(ClipEmbedder {
+                    backbone: Backbone::SigLip { model, config },
+                    tokenizer,
+                    device,
+                    dtype,
+                })
+            }
+        }
+    }
+
     /// Generate a 512-dimensional embedding for an image
-    /// 
+    ///
     /// # Arguments
     /// * `image_path` - Path to the image file
-    /// 
+    ///
     /// # Returns
-    /// A vector of 512 floating point values representing the image embedding
+    /// A vector of floating point values representing the image embedding
     pub fn get_image_embedding(&self, image_path: &str) -> Result<Vec<f32>> {
-        let img = load_image(image_path, self.config.image_size)?;
-        let img = img.unsqueeze(0)?.to_device(&self.device)?;
-        let image_features = self.model.get_image_features(&img)?;
+        let img = load_image(image_path, &self.backbone.preprocessing())?;
+        let img = img.unsqueeze(0)?.to_device(&self.device)?.to_dtype(self.dtype)?;
+        let image_features = self.get_image_features(&img)?.to_dtype(DType::F32)?;
         let embedding = image_features.squeeze(0)?.to_vec1::<f32>()?;
         Ok(embedding)
     }
-    
+
     /// Generate a 512-dimensional embedding for a text string
-    /// 
+    ///
     /// # Arguments
     /// * `text` - The text string to encode
-    /// 
+    ///
     /// # Returns
-    /// A vector of 512 floating point values representing the text embedding
+    /// A vector of floating point values representing the text embedding
     pub fn get_text_embedding(&self, text: &str) -> Result<Vec<f32>> {
-        let encoding = self.tokenizer.encode(text, true)
-            .map_err(anyhow::Error::msg)?;
-        let tokens = encoding.get_ids().to_vec();
-        
-        // Create input tensor with batch dimension
-        let input_ids = Tensor::new(vec![tokens], &self.device)?;
-        let text_features = self.model.get_text_features(&input_ids)?;
+        let input_ids = self.encode_texts(&[text])?;
+        let text_features = self.get_text_features(&input_ids)?.to_dtype(DType::F32)?;
         let embedding = text_features.squeeze(0)?.to_vec1::<f32>()?;
         Ok(embedding)
     }
+
+    /// Generate embeddings for a batch of images in a single forward pass
+    ///
+    /// This stacks every image into one `(batch, channels, height, width)` tensor before
+    /// calling the model, which avoids the per-call overhead of `get_image_embedding` when
+    /// embedding many images (especially on GPU).
+    ///
+    /// # Arguments
+    /// * `image_paths` - Paths to the image files
+    ///
+    /// # Returns
+    /// One embedding per input image, in the same order as `image_paths`
+    pub fn get_image_embeddings(&self, image_paths: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let preprocessing = self.backbone.preprocessing();
+        let images = image_paths
+            .iter()
+            .map(|path| load_image(path, &preprocessing))
+            .collect::<Result<Vec<_>>>()?;
+        let batch = Tensor::stack(&images, 0)?.to_device(&self.device)?.to_dtype(self.dtype)?;
+        let image_features = self.get_image_features(&batch)?.to_dtype(DType::F32)?;
+        tensor_rows_to_vecs(&image_features)
+    }
+
+    /// Generate embeddings for a batch of text strings in a single forward pass
+    ///
+    /// Token id sequences are padded to a common length with the tokenizer's pad token before
+    /// being stacked into one `(batch, seq_len)` tensor.
+    ///
+    /// # Arguments
+    /// * `texts` - The text strings to encode
+    ///
+    /// # Returns
+    /// One embedding per input text, in the same order as `texts`
+    pub fn get_text_embeddings(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let input_ids = self.encode_texts(texts)?;
+        let text_features = self.get_text_features(&input_ids)?.to_dtype(DType::F32)?;
+        tensor_rows_to_vecs(&text_features)
+    }
+
+    /// Zero-shot classify an image against a set of candidate labels
+    ///
+    /// Runs the backbone's actual contrastive scoring rather than raw cosine similarity: CLIP
+    /// softmaxes the scaled logits over labels, while SigLIP applies a per-label sigmoid.
+    ///
+    /// # Arguments
+    /// * `image` - The image to classify
+    /// * `labels` - Candidate labels, e.g. `["a photo of a cat", "a photo of a dog"]`
+    ///
+    /// # Returns
+    /// `(label, probability)` pairs in the same order as `labels`
+    pub fn classify(&self, image: &image::DynamicImage, labels: &[&str]) -> Result<Vec<(String, f32)>> {
+        let pixel_values = preprocess_image(image, &self.backbone.preprocessing())?
+            .unsqueeze(0)?
+            .to_device(&self.device)?
+            .to_dtype(self.dtype)?;
+        let input_ids = self.encode_texts(labels)?;
+
+        let probs = match &self.backbone {
+            Backbone::Clip { model, .. } => {
+                let (_logits_per_text, logits_per_image) = model.forward(&pixel_values, &input_ids)?;
+                candle_nn::ops::softmax(&logits_per_image.to_dtype(DType::F32)?, 1)?
+                    .squeeze(0)?
+                    .to_vec1::<f32>()?
+            }
+            Backbone::SigLip { model, .. } => {
+                let (_logits_per_text, logits_per_image) = model.forward(&pixel_values, &input_ids)?;
+                candle_nn::ops::sigmoid(&logits_per_image.to_dtype(DType::F32)?)?
+                    .squeeze(0)?
+                    .to_vec1::<f32>()?
+            }
+        };
+
+        Ok(labels.iter().map(|label| label.to_string()).zip(probs).collect())
+    }
+
+    fn get_image_features(&self, pixel_values: &Tensor) -> Result<Tensor> {
+        match &self.backbone {
+            Backbone::Clip { model, .. } => Ok(model.get_image_features(pixel_values)?),
+            Backbone::SigLip { model, .. } => Ok(model.get_image_features(pixel_values)?),
+        }
+    }
+
+    fn get_text_features(&self, input_ids: &Tensor) -> Result<Tensor> {
+        match &self.backbone {
+            Backbone::Clip { model, .. } => Ok(model.get_text_features(input_ids)?),
+            Backbone::SigLip { model, .. } => Ok(model.get_text_features(input_ids)?),
+        }
+    }
+
+    /// Tokenize and pad a batch of strings into one `(batch, seq_len)` tensor
+    ///
+    /// CLIP adds BOS/EOS tokens and pads with `<|endoftext|>` to the longest sequence in the
+    /// batch. SigLIP pads/truncates to a fixed length of 64 with no BOS/EOS insertion, matching
+    /// how each backbone was trained.
+    fn encode_texts(&self, texts: &[&str]) -> Result<Tensor> {
+        match &self.backbone {
+            Backbone::Clip { .. } => {
+                let encodings = texts
+                    .iter()
+                    .map(|text| self.tokenizer.encode(*text, true).map_err(anyhow::Error::msg))
+                    .collect::<Result<Vec<_>>>()?;
+                let max_len = encodings.iter().map(|e| e.get_ids().len()).max().unwrap_or(0);
+                let pad_id = self.tokenizer.token_to_id("<|endoftext|>").unwrap_or(0);
+
+                let padded: Vec<Vec<u32>> = encodings
+                    .iter()
+                    .map(|encoding| {
+                        let mut ids = encoding.get_ids().to_vec();
+                        ids.resize(max_len, pad_id);
+                        ids
+                    })
+                    .collect();
+                Ok(Tensor::new(padded, &self.device)?)
+            }
+            Backbone::SigLip { .. } => {
+                const SEQ_LEN: usize = 64;
+                let pad_id = self.tokenizer.token_to_id("<pad>").unwrap_or(1);
+
+                let padded: Vec<Vec<u32>> = texts
+                    .iter()
+                    .map(|text| {
+                        let encoding = self.tokenizer.encode(*text, false).map_err(anyhow::Error::msg)?;
+                        let mut ids = encoding.get_ids().to_vec();
+                        ids.truncate(SEQ_LEN);
+                        ids.resize(SEQ_LEN, pad_id);
+                        Ok(ids)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Tensor::new(padded, &self.device)?)
+            }
+        }
+    }
+}
+
+/// L2-normalize an embedding so its components sum-of-squares to 1
+pub fn normalize(embedding: &[f32]) -> Vec<f32> {
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        embedding.to_vec()
+    } else {
+        embedding.iter().map(|x| x / norm).collect()
+    }
+}
+
+/// Cosine similarity between two embeddings of equal length
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot_product / (norm_a * norm_b)
+    }
+}
+
+/// Split a `(batch, dim)` tensor into one `Vec<f32>` per row
+fn tensor_rows_to_vecs(tensor: &Tensor) -> Result<Vec<Vec<f32>>> {
+    let batch_size = tensor.dim(0)?;
+    (0..batch_size)
+        .map(|i| Ok(tensor.get(i)?.to_vec1::<f32>()?))
+        .collect()
 }
 
 fn get_device(cpu: bool) -> Result<Device> {
@@ -109,31 +436,60 @@ fn get_device(cpu: bool) -> Result<Device> {
     }
 }
 
-fn load_image<T: AsRef<std::path::Path>>(path: T, image_size: usize) -> Result<Tensor> {
+fn load_image<T: AsRef<std::path::Path>>(path: T, preprocessing: &Preprocessing) -> Result<Tensor> {
     let img = image::ImageReader::open(path)?.decode()?;
-    let (height, width) = (image_size, image_size);
-    let img = img.resize_to_fill(
-        width as u32,
-        height as u32,
-        image::imageops::FilterType::Triangle,
-    );
-    let img = img.to_rgb8();
-    let img = img.into_raw();
-    let img = Tensor::from_vec(img, (height, width, 3), &Device::Cpu)?
+    preprocess_image(&img, preprocessing)
+}
+
+/// Resize and normalize per channel, center-cropping first if `preprocessing.center_crop` is set
+fn preprocess_image(img: &image::DynamicImage, preprocessing: &Preprocessing) -> Result<Tensor> {
+    let size = preprocessing.image_size as u32;
+    let prepared = if preprocessing.center_crop {
+        let resized = resize_shorter_side(img, size);
+        center_crop(&resized, size)
+    } else {
+        img.resize_exact(size, size, image::imageops::FilterType::Triangle)
+    };
+
+    let pixels = prepared.to_rgb8().into_raw();
+    let mean = Tensor::new(&preprocessing.mean, &Device::Cpu)?.reshape((3, 1, 1))?;
+    let std = Tensor::new(&preprocessing.std, &Device::Cpu)?.reshape((3, 1, 1))?;
+
+    let img = Tensor::from_vec(pixels, (size as usize, size as usize, 3), &Device::Cpu)?
         .permute((2, 0, 1))?
         .to_dtype(DType::F32)?
-        .affine(2. / 255., -1.)?;
+        .affine(1. / 255., 0.)?
+        .broadcast_sub(&mean)?
+        .broadcast_div(&std)?;
     Ok(img)
 }
 
-fn get_tokenizer(tokenizer: Option<String>) -> Result<Tokenizer> {
+/// Resize so the shorter side equals `target`, preserving aspect ratio
+fn resize_shorter_side(img: &image::DynamicImage, target: u32) -> image::DynamicImage {
+    let (width, height) = (img.width(), img.height());
+    let (new_width, new_height) = if width <= height {
+        (target, ((height as u64 * target as u64) / width as u64) as u32)
+    } else {
+        (((width as u64 * target as u64) / height as u64) as u32, target)
+    };
+    img.resize_exact(new_width, new_height, image::imageops::FilterType::CatmullRom)
+}
+
+/// Crop the center `size x size` square out of an image at least that large on both axes
+fn center_crop(img: &image::DynamicImage, size: u32) -> image::DynamicImage {
+    let x = (img.width().saturating_sub(size)) / 2;
+    let y = (img.height().saturating_sub(size)) / 2;
+    img.crop_imm(x, y, size, size)
+}
+
+fn get_tokenizer(tokenizer: Option<String>, repo_id: &str, revision: &str) -> Result<Tokenizer> {
     let tokenizer_file = match tokenizer {
         None => {
             let api = hf_hub::api::sync::Api::new()?;
             let api = api.repo(hf_hub::Repo::with_revision(
-                "openai/clip-vit-base-patch32".to_string(),
+                repo_id.to_string(),
                 hf_hub::RepoType::Model,
-                "refs/pr/15".to_string(),
+                revision.to_string(),
             ));
             api.get("tokenizer.json")?
         }
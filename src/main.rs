@@ -1,6 +1,6 @@
 use anyhow::Result;
 use clap::Parser;
-use clipper::ClipEmbedder;
+use clipper::{ClipEmbedder, ModelKind, Precision, SigLipVariant, cosine_similarity};
 
 #[derive(Parser)]
 struct Args {
@@ -18,14 +18,37 @@ struct Args {
 
     #[arg(long, use_value_delimiter = true)]
     sequences: Option<Vec<String>>,
+
+    /// Backbone to load: "clip", "siglip-base-224", or "siglip-base-384"
+    #[arg(long, default_value = "clip")]
+    model_kind: String,
+
+    /// Numeric precision for weights and inputs: "f32", "f16", or "bf16" (forced to f32 on CPU)
+    #[arg(long, default_value = "f32")]
+    precision: String,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    
+
+    let model_kind = match args.model_kind.as_str() {
+        "clip" => ModelKind::ClipVitBasePatch32,
+        "siglip" | "siglip-base-224" => ModelKind::SigLip(SigLipVariant::Base224),
+        "siglip-base-384" => ModelKind::SigLip(SigLipVariant::Base384),
+        other => anyhow::bail!(
+            "unknown --model-kind '{other}' (expected clip, siglip-base-224, or siglip-base-384)"
+        ),
+    };
+    let precision = match args.precision.as_str() {
+        "f32" => Precision::F32,
+        "f16" => Precision::F16,
+        "bf16" => Precision::Bf16,
+        other => anyhow::bail!("unknown --precision '{other}' (expected f32, f16, or bf16)"),
+    };
+
     // Create the ClipEmbedder instance
     println!("🚀 Initializing CLIP embedder...");
-    let embedder = ClipEmbedder::new(args.model, args.tokenizer, args.cpu)?;
+    let embedder = ClipEmbedder::new(args.model, args.tokenizer, args.cpu, model_kind, precision)?;
     println!("✅ CLIP embedder initialized successfully!\n");
     
     // Get image paths
@@ -113,15 +136,3 @@ fn main() -> Result<()> {
 
     Ok(())
 }
-
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-    
-    if norm_a == 0.0 || norm_b == 0.0 {
-        0.0
-    } else {
-        dot_product / (norm_a * norm_b)
-    }
-}